@@ -1,74 +1,245 @@
 // imports
-use std::{fs, path::{Path, PathBuf}};
-use chrono::{DateTime, Utc};  // date/time parsing & formatting
-use clap::Parser;  // terminal argument parser
+use std::{collections::HashSet, fs::File, path::{Path, PathBuf}};
+use chrono::{DateTime, NaiveDate};  // date/time parsing & formatting
+use clap::{Parser, ValueEnum};  // terminal argument parser
+use flate2::read::GzDecoder;  // transparent decompression for `.tar.gz`/`.tgz`
+use glob::Pattern;  // glob matching for `--exclude`
+use openat::{Dir, SimpleType};  // directory-handle based traversal (fstatat instead of path re-resolution)
 use owo_colors::OwoColorize;  // colored text
+use regex::Regex;  // regex matching for `--exclude`
 use serde::Serialize;  // structs -> json
 use strum::Display;  // format Enum variants as  strings easily
-use tabled::{Table, Tabled, settings::Style};  // print ASCII tables in terminals
+use tabled::{Table, Tabled, settings::{Disable, Style, object::Columns}};  // print ASCII tables in terminals
+use users::{get_group_by_gid, get_user_by_uid};  // resolve uid/gid -> names
 
 // Structures
 // #[derive(...)]: Debug(for printing with {:?}); Display(for printing with {} (from strum)); Serialize(for converting to JSON)
+// The full set of Unix file kinds, in the order `ls -l`'s leading letter would list them (-, d, l, b, c, s, p).
 #[derive(Debug, Display, Serialize)]
-enum FileType { File, Directory}
+enum FileType { File, Directory, Symlink, BlockDevice, CharDevice, Socket, Fifo }
 
-#[derive(Debug, Tabled, Serialize)] 
+// Keys the listing can be ordered by, picked with `--sort <key>`.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum SortKey { Name, Size, Date, Extension, Kind }
+
+#[derive(Debug, Tabled, Serialize)]
 struct FileMetadata {
     // The #[tabled(rename = "...")] attribute changes the column header name in the output table.
     #[tabled(rename="Name")] name: String,
     #[tabled(rename="Type")] ftype: FileType,
     #[tabled(rename="Size")] size: String,
-    #[tabled(rename="Last Modified")] modified: String
+    // Raw byte count backing `size`, kept around so sorting doesn't have to parse "1.5GB" strings.
+    // Hidden from the table but still serialized to JSON since it's genuinely useful there.
+    #[tabled(skip)] size_bytes: u64,
+    #[tabled(rename="Last Modified")] modified: String,
+    // Raw mtime (seconds since epoch) backing `modified`, kept around so `--sort date` orders by time
+    // instead of lexically comparing "%a %e %b %y" strings (which sorts by weekday, then day of month).
+    #[tabled(skip)] mtime_secs: i64,
+    // Only populated (and only shown in the table) when `-l`/`--long` is passed.
+    #[tabled(rename="Permissions", display_with = "display_opt")] permissions: Option<String>,
+    #[tabled(rename="Owner", display_with = "display_opt")] owner: Option<String>,
+    #[tabled(rename="Group", display_with = "display_opt")] group: Option<String>
 }
 
+// tabled needs a plain `Display`-style formatter for `Option<String>` columns; we just show "" for None.
+fn display_opt(value: &Option<String>) -> String { value.clone().unwrap_or_default() }
+
 #[derive(Debug, Parser)]
 #[command(version, about="better ls", long_about="better version of the commonly used command `ls`")]
 struct Cli {
     path: Option<PathBuf>,
-    #[arg(short, long)] json: bool
+    #[arg(short, long)] json: bool,
+    /// Sort entries by this key instead of raw `read_dir` order.
+    #[arg(long, value_enum)] sort: Option<SortKey>,
+    /// Reverse the sort order (has no effect without `--sort`).
+    #[arg(long)] reverse: bool,
+    /// Show permissions, owner and group, like `ls -l`.
+    #[arg(short='l', long)] long: bool,
+    /// Recursive disk-usage tree instead of a flat listing.
+    #[arg(long)] tree: bool,
+    /// How many levels deep `--tree` should recurse.
+    #[arg(short='d', long, default_value_t = 1)] depth: usize,
+    /// Fold entries smaller than this threshold into a single `<aggregated>` row per directory.
+    /// Accepts a bare byte count or a K/M/G suffix (1024-based, same units as file sizes).
+    #[arg(long)] aggr: Option<String>,
+    /// Skip dotfiles/dot-directories.
+    #[arg(long)] no_hidden: bool,
+    /// Skip entries whose name matches this glob or regex pattern. Repeatable.
+    #[arg(long)] exclude: Vec<String>,
+    /// Peek inside `.tar`/`.tar.gz`/`.zip` files encountered in a listing, rather than showing them as
+    /// an opaque file. Has no effect on an archive passed directly as `path` -- that's always browsed.
+    #[arg(long)] archives: bool,
+    /// Sum actual allocated disk blocks (like `du`) instead of apparent file length.
+    #[arg(short='u', long="disk-usage")] disk_usage: bool,
+    /// Print raw, unformatted byte counts instead of "1.5GB"-style strings.
+    #[arg(long)] bytes: bool
+}
+
+// Bundles the display-affecting flags (as opposed to `Filters`, which narrows *which* entries are shown)
+// so the traversal functions don't need an ever-growing list of bool parameters.
+struct ListOptions { long: bool, bytes: bool, disk_usage: bool, archives: bool }
+
+impl ListOptions {
+    fn from_cli(cli: &Cli) -> Self {
+        ListOptions { long: cli.long, bytes: cli.bytes, disk_usage: cli.disk_usage, archives: cli.archives }
+    }
+}
+
+// Formats a byte count either as a raw number (`--bytes`, for scripting) or the usual "1.5GB" string.
+fn format_size(size: u64, bytes: bool) -> String {
+    if bytes { size.to_string() } else { convert_binary_units(size) }
+}
+
+// A single `--exclude` pattern, matched against a bare file name (not the full path), and always against
+// the *whole* name -- never a substring. Precedence: a spec containing glob metacharacters (`* ? [ ]`) is
+// treated as a glob; anything else is treated as a regex, anchored with `^(?:...)$` so a plain literal
+// like `node_modules` means "equals node_modules", not "contains node_modules".
+enum ExcludePattern { Regex(Regex), Glob(Pattern) }
+
+impl ExcludePattern {
+    fn parse(spec: &str) -> Self {
+        if spec.contains(['*', '?', '[', ']']) {
+            return ExcludePattern::Glob(Pattern::new(spec).unwrap_or_else(|_| Pattern::new("").unwrap()));
+        }
+        match Regex::new(&format!("^(?:{spec})$")) {
+            Ok(re) => ExcludePattern::Regex(re),
+            // Not valid regex either (e.g. unbalanced parens) -- fall back to an exact-literal glob.
+            Err(_) => ExcludePattern::Glob(Pattern::new(spec).unwrap_or_else(|_| Pattern::new("").unwrap()))
+        }
+    }
+
+    fn is_match(&self, name: &str) -> bool {
+        match self {
+            ExcludePattern::Regex(re) => re.is_match(name),
+            ExcludePattern::Glob(pattern) => pattern.matches(name)
+        }
+    }
+}
+
+// Bundles the filtering flags so `fetchfiles`/`dir_size`/`print_tree` don't need a growing argument list.
+struct Filters { no_hidden: bool, exclude: Vec<ExcludePattern> }
+
+impl Filters {
+    fn from_cli(cli: &Cli) -> Self {
+        Filters { no_hidden: cli.no_hidden, exclude: cli.exclude.iter().map(|p| ExcludePattern::parse(p)).collect() }
+    }
+
+    fn excludes(&self, name: &str) -> bool {
+        (self.no_hidden && name.starts_with('.')) || self.exclude.iter().any(|p| p.is_match(name))
+    }
 }
 
 fn main() {
     let cli = Cli::parse();  // Parses args passed in terminal
     let path = cli.path.unwrap_or(PathBuf::from("."));
-    if let Ok(does_exist) = fs::exists(&path) {  // checks if path exists; fs::exists is the newer api
-        if does_exist {
-            let files = fetchfiles(&path);  // gets the list of 'FileMetadata' objects
-            if files.is_empty() { println!("{}", "The folder is empty".red()); } else {
-                let mut f_table = Table::new(files);  // Create a new Table from the vector of files.
-                f_table.with(Style::rounded());  // Apply a rounded visual style to the table borders.
-                if cli.json {
-                    // Serialize the data again to JSON string and print it.
-                    // We call fetchfiles(&path) again here, which is slightly inefficient (fetching twice), but safe.
-                    println!("{}", serde_json::to_string(&fetchfiles(&path)).unwrap_or("Can't parse json".to_string()));
-                } else { println!("{}", f_table); }
+    let filters = Filters::from_cli(&cli);
+    let opts = ListOptions::from_cli(&cli);
+
+    // An archive passed directly as `path` is always browsed as if it were a directory.
+    if path.is_file() && is_archive(&path) {
+        let mut files = fetch_archive(&path, opts.bytes);
+        sort_files(&mut files, cli.sort, cli.reverse);
+        render(files, cli.json, opts.long);
+        return;
+    }
+
+    // Open the target once as a directory handle; every traversal below stats children relative to this
+    // `fd` via fstatat instead of re-resolving a full path for each entry.
+    match Dir::open(&path) {
+        Ok(dir) => {
+            if cli.tree {
+                let threshold = cli.aggr.as_deref().map(parse_threshold).unwrap_or(0);
+                print_tree(&dir, 0, cli.depth, threshold, &filters, &opts);
+                return;
             }
-        } else { println!("{}", "Path does not exist.".red()); }
-    } else { println!("{}", "Error reading directory.".red()); }  // If the operating system failed to check the directory
+            let mut files = fetchfiles(&dir, &opts, &filters);  // gets the list of 'FileMetadata' objects (walked once; `render` reuses this Vec for JSON too)
+            sort_files(&mut files, cli.sort, cli.reverse);
+            render(files, cli.json, opts.long);
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => println!("{}", "Path does not exist.".red()),
+        Err(_) => println!("{}", "Error reading directory.".red())
+    }
 }
 
-fn fetchfiles(path: &Path) -> Vec<FileMetadata> {
+// Shared by the normal directory listing and the `--archives`/archive-as-path case: prints either a
+// rounded ASCII table or (with `--json`) the raw serialized entries.
+fn render(files: Vec<FileMetadata>, json: bool, long: bool) {
+    if files.is_empty() { println!("{}", "The folder is empty".red()); return; }
+    let mut f_table = Table::new(&files);  // Create a new Table from the vector of files.
+    f_table.with(Style::rounded());  // Apply a rounded visual style to the table borders.
+    if !long {
+        // Permissions/Owner/Group are columns 4, 5 and 6 (Name, Type, Size, Last Modified come first);
+        // drop them entirely rather than printing them empty.
+        f_table.with(Disable::column(Columns::new(4..7)));
+    }
+    if json {
+        println!("{}", serde_json::to_string(&files).unwrap_or("Can't parse json".to_string()));
+    } else { println!("{}", f_table); }
+}
+
+// Orders `files` in place by the requested key, leaving raw `read_dir` order untouched when `key` is None.
+fn sort_files(files: &mut [FileMetadata], key: Option<SortKey>, reverse: bool) {
+    if let Some(key) = key {
+        files.sort_by(|a, b| match key {
+            SortKey::Name => a.name.cmp(&b.name),
+            SortKey::Size => a.size_bytes.cmp(&b.size_bytes),
+            SortKey::Date => a.mtime_secs.cmp(&b.mtime_secs),
+            SortKey::Extension => extension_of(&a.name).cmp(extension_of(&b.name)),
+            // Directories before files, then fall back to name so the grouping is stable.
+            SortKey::Kind => matches!(b.ftype, FileType::Directory).cmp(&matches!(a.ftype, FileType::Directory))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        if reverse { files.reverse(); }
+    }
+}
+
+// Everything after the last '.' in a file name, or "" for extensionless files/dotfiles like ".gitignore".
+fn extension_of(name: &str) -> &str {
+    match name.rfind('.') {
+        Some(0) | None => "",  // leading dot (dotfile) doesn't count as an extension
+        Some(i) => &name[i + 1..]
+    }
+}
+
+fn fetchfiles(dir: &Dir, opts: &ListOptions, filters: &Filters) -> Vec<FileMetadata> {
     let mut data = Vec::default(); // Initialize empty vector
-    if let Ok(content) = fs::read_dir(path) {
+    if let Ok(content) = dir.list_dir(".") {
         for entry in content {  // Loop through every entry in the directory.
             if let Ok(file) = entry {  // Check if the entry is valid (not a corrupted link or read error).
-                if let Ok(meta) = fs::metadata(&file.path()) {  // Get the metadata (size, permissions, etc.) for the specific file.
+                let name = file.file_name().to_string_lossy().into_owned();
+                if filters.excludes(&name) { continue; }
+                // `dir.metadata` is an fstatat relative to the already-open handle, not a fresh path lookup,
+                // and (like symlink_metadata) doesn't follow symlinks, so a link is classified as a Symlink.
+                if let Ok(meta) = dir.metadata(file.file_name()) {
+                    let stat = meta.stat();
+                    let is_dir = meta.simple_type() == SimpleType::Dir;
+                    let own_size = if opts.disk_usage { stat.st_blocks as u64 * 512 } else { stat.st_size as u64 };
+                    let size_bytes = if is_dir {
+                        // Open the child as its own `Dir` handle and recurse, rather than rebuilding a `PathBuf`.
+                        dir.sub_dir(file.file_name()).map(|sub| dir_size(&sub, filters, opts.disk_usage)).unwrap_or(0)
+                    } else { own_size };
+
+                    // `--archives` peeks inside any archive file right here, nesting its members under it.
+                    if opts.archives && !is_dir && is_archive(Path::new(&name)) {
+                        data.extend(fetch_archive(&dir.recover_path().unwrap_or_default().join(&name), opts.bytes)
+                            .into_iter().map(|mut member| { member.name = format!("{name}/{}", member.name); member }));
+                    }
+
                     data.push(FileMetadata {  // Create our custom struct and push it into the vector.
-                        name: file.file_name().into_string().unwrap_or("???".into()),
-                        ftype: if meta.is_dir() { FileType::Directory } else { FileType::File },
-                        size: if meta.is_dir() {
-                            // If it's a directory, we must check if it's empty or calculate recursive size.
-                            match is_dir_empty(&file.path()) {
-                                Ok(true) => "0B".to_string(), // Empty dir
-                                // If not empty, call recursive function `dir_size` and format the bytes.
-                                Ok(false) => convert_binary_units(dir_size(&file.path())),
-                                Err(_) => "0B".to_string() // Error reading dir
-                            }
-                        } else { convert_binary_units(meta.len()) },  // normal file
-
-                        modified: if let Ok(modif) = meta.modified() {
-                            let date: DateTime<Utc> = modif.into(); format!("{}", date.format("%a %e %b %y"))
-                        } else { String::default() },
+                        name,
+                        ftype: classify(stat.st_mode),
+                        size: format_size(size_bytes, opts.bytes),
+                        size_bytes,
+
+                        modified: format_mtime(stat.st_mtime),
+                        mtime_secs: stat.st_mtime,
+
+                        // Only bother resolving permissions/owner/group when `-l` was actually passed.
+                        permissions: opts.long.then(|| mode_to_string(stat.st_mode as u32)),
+                        owner: opts.long.then(|| get_user_by_uid(stat.st_uid)
+                            .map(|u| u.name().to_string_lossy().into_owned()).unwrap_or_else(|| stat.st_uid.to_string())),
+                        group: opts.long.then(|| get_group_by_gid(stat.st_gid)
+                            .map(|g| g.name().to_string_lossy().into_owned()).unwrap_or_else(|| stat.st_gid.to_string())),
                     });
                 }
             }
@@ -77,16 +248,139 @@ fn fetchfiles(path: &Path) -> Vec<FileMetadata> {
     data // Return
 }
 
-fn dir_size(path: &Path) -> u64 {
+// Whether `path`'s name looks like a browsable archive (matched by extension, not content-sniffed).
+fn is_archive(path: &Path) -> bool {
+    let name = path.to_string_lossy().to_lowercase();
+    name.ends_with(".tar") || name.ends_with(".tar.gz") || name.ends_with(".tgz") || name.ends_with(".zip")
+}
+
+// Reads an archive's member list as if it were a directory listing: one `FileMetadata` row per entry,
+// with uncompressed size and stored mtime formatted the same way a normal file's would be.
+fn fetch_archive(path: &Path, bytes: bool) -> Vec<FileMetadata> {
+    let name = path.to_string_lossy().to_lowercase();
+    if name.ends_with(".zip") { fetch_zip(path, bytes) } else { fetch_tar(path, bytes) } // covers .tar and .tar.gz/.tgz
+}
+
+fn fetch_tar(path: &Path, bytes: bool) -> Vec<FileMetadata> {
+    let mut data = Vec::default();
+    let Ok(file) = File::open(path) else { return data };
+    let name = path.to_string_lossy().to_lowercase();
+    let reader: Box<dyn std::io::Read> = if name.ends_with(".gz") || name.ends_with(".tgz") {
+        Box::new(GzDecoder::new(file))
+    } else { Box::new(file) };
+
+    let mut archive = tar::Archive::new(reader);
+    if let Ok(entries) = archive.entries() {
+        for entry in entries.flatten() {
+            let Ok(entry_path) = entry.path() else { continue };
+            let is_dir = entry.header().entry_type().is_dir();
+            let size = entry.header().size().unwrap_or(0);
+            let mtime_secs = entry.header().mtime().unwrap_or(0) as i64;
+            data.push(FileMetadata {
+                name: entry_path.to_string_lossy().into_owned(),
+                ftype: if is_dir { FileType::Directory } else { FileType::File },
+                size: format_size(size, bytes),
+                size_bytes: size,
+                modified: format_mtime(mtime_secs),
+                mtime_secs,
+                permissions: None, owner: None, group: None
+            });
+        }
+    }
+    data
+}
+
+fn fetch_zip(path: &Path, bytes: bool) -> Vec<FileMetadata> {
+    let mut data = Vec::default();
+    let Ok(file) = File::open(path) else { return data };
+    let Ok(mut archive) = zip::ZipArchive::new(file) else { return data };
+    for i in 0..archive.len() {
+        let Ok(member) = archive.by_index(i) else { continue };
+        let size = member.size();
+        let mtime = member.last_modified();
+        data.push(FileMetadata {
+            name: member.name().to_string(),
+            ftype: if member.is_dir() { FileType::Directory } else { FileType::File },
+            size: format_size(size, bytes),
+            size_bytes: size,
+            modified: format_zip_mtime(mtime),
+            mtime_secs: zip_mtime_secs(mtime),
+            permissions: None, owner: None, group: None
+        });
+    }
+    data
+}
+
+// Converts a zip member's stored (non-timezone-aware) DOS timestamp into the same "%a %e %b %y" format
+// the rest of `betterls` uses for `modified`.
+fn format_zip_mtime(dt: zip::DateTime) -> String {
+    zip_mtime_naive(dt).map(|naive| format!("{}", naive.format("%a %e %b %y"))).unwrap_or_default()
+}
+
+// The same DOS timestamp, as seconds since the epoch, so `--sort date` can order zip members numerically
+// rather than comparing the formatted string.
+fn zip_mtime_secs(dt: zip::DateTime) -> i64 {
+    zip_mtime_naive(dt).map(|naive| naive.and_utc().timestamp()).unwrap_or(0)
+}
+
+fn zip_mtime_naive(dt: zip::DateTime) -> Option<chrono::NaiveDateTime> {
+    NaiveDate::from_ymd_opt(dt.year() as i32, dt.month() as u32, dt.day() as u32)
+        .and_then(|date| date.and_hms_opt(dt.hour() as u32, dt.minute() as u32, dt.second() as u32))
+}
+
+// Classifies a file's kind from its raw `st_mode`, covering the full set of Unix file types.
+fn classify(mode: libc::mode_t) -> FileType {
+    match mode & libc::S_IFMT {
+        libc::S_IFDIR => FileType::Directory,
+        libc::S_IFLNK => FileType::Symlink,
+        libc::S_IFBLK => FileType::BlockDevice,
+        libc::S_IFCHR => FileType::CharDevice,
+        libc::S_IFSOCK => FileType::Socket,
+        libc::S_IFIFO => FileType::Fifo,
+        _ => FileType::File
+    }
+}
+
+// Formats a `stat` mtime (seconds since epoch) the same way the old `SystemTime`-based code did.
+fn format_mtime(mtime_secs: i64) -> String {
+    DateTime::from_timestamp(mtime_secs, 0)
+        .map(|date| format!("{}", date.format("%a %e %b %y")))
+        .unwrap_or_default()
+}
+
+// Formats the permission bits of a Unix mode as the familiar "rwxr-xr-x" string (owner/group/other).
+fn mode_to_string(mode: u32) -> String {
+    let bit = |shift: u32, c: char| if mode & (1 << shift) != 0 { c } else { '-' };
+    [8, 7, 6, 5, 4, 3, 2, 1, 0].iter()
+        .zip(['r', 'w', 'x', 'r', 'w', 'x', 'r', 'w', 'x'])
+        .map(|(&shift, c)| bit(shift, c))
+        .collect()
+}
+
+// Recursive, 'fd'-relative total: opens each subdirectory once (via `Dir::sub_dir`) and stats its
+// children with `Dir::metadata`, rather than re-resolving a full path at every level. Excluded entries
+// are skipped so their bytes don't inflate the parent's total. With `disk_usage`, sums actual allocated
+// blocks (`st_blocks * 512`) instead of apparent length, and de-duplicates hard-linked files by their
+// `(st_dev, st_ino)` pair so a file linked twice within this tree is only counted once.
+fn dir_size(dir: &Dir, filters: &Filters, disk_usage: bool) -> u64 {
+    let mut seen_inodes = HashSet::new();
+    dir_size_rec(dir, filters, disk_usage, &mut seen_inodes)
+}
+
+fn dir_size_rec(dir: &Dir, filters: &Filters, disk_usage: bool, seen_inodes: &mut HashSet<(u64, u64)>) -> u64 {
     let mut total = 0;
-    if let Ok(entries) = fs::read_dir(path) {
+    if let Ok(entries) = dir.list_dir(".") {
         for entry in entries.flatten() {  // 'flatten' removes Err results, giving us only valid entries.
-            let p = entry.path();
-            if let Ok(meta) = fs::symlink_metadata(&p) {  // Use symlink_metadata so we don't follow symlinks (preventing infinite loops)
-                if meta.is_dir() {
-                    total += dir_size(&p);  // RECURSION: If this entry is a directory, call this function again on it.
-                } else {
-                    total += meta.len();  // If it's a file, add its size to the total.
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if filters.excludes(&name) { continue; }
+            if let Ok(meta) = dir.metadata(entry.file_name()) {
+                let stat = meta.stat();
+                if meta.simple_type() == SimpleType::Dir {
+                    // RECURSION: open the child directory and call this function again on it.
+                    total += dir.sub_dir(entry.file_name()).map(|sub| dir_size_rec(&sub, filters, disk_usage, seen_inodes)).unwrap_or(0);
+                } else if seen_inodes.insert((stat.st_dev as u64, stat.st_ino as u64)) {
+                    // Only the first time we see this (dev, inode) pair counts -- later hard links are free.
+                    total += if disk_usage { stat.st_blocks as u64 * 512 } else { stat.st_size as u64 };
                 }
             }
         }
@@ -94,9 +388,97 @@ fn dir_size(path: &Path) -> u64 {
     total
 }
 
-// checks if a directory has any children.
-// It reads the directory and fetchs the 'next' item; If 'next()' is None, directory is empty
-fn is_dir_empty(path: &Path) -> std::io::Result<bool> { Ok(fs::read_dir(path)?.next().is_none()) }
+// A directory entry with its subtree size and (for directories) already-sized children, built by a single
+// walk of the filesystem so each directory is opened/stat'd exactly once for the whole tree, regardless of
+// how many ancestor levels end up being printed.
+struct SizedEntry { name: String, is_dir: bool, size: u64, children: Vec<SizedEntry> }
+
+// Opens and stats every entry under `dir` exactly once, recursing fully into subdirectories so each one's
+// size is the sum of its own children -- computed bottom-up in this same pass instead of being re-derived
+// by a separate `dir_size` walk per ancestor. `seen_inodes` is threaded through the whole walk so hard
+// links are only counted once across the entire tree, not just within a single subdirectory.
+fn build_sized_tree(dir: &Dir, filters: &Filters, disk_usage: bool, seen_inodes: &mut HashSet<(u64, u64)>) -> Vec<SizedEntry> {
+    let mut entries = Vec::new();
+    if let Ok(content) = dir.list_dir(".") {
+        for file in content.flatten() {
+            let name = file.file_name().to_string_lossy().into_owned();
+            if filters.excludes(&name) { continue; }
+            let Ok(meta) = dir.metadata(file.file_name()) else { continue };
+            let stat = meta.stat();
+            let is_dir = meta.simple_type() == SimpleType::Dir;
+            let (size, children) = if is_dir {
+                match dir.sub_dir(file.file_name()) {
+                    Ok(sub) => {
+                        let children = build_sized_tree(&sub, filters, disk_usage, seen_inodes);
+                        (children.iter().map(|c| c.size).sum(), children)
+                    }
+                    Err(_) => (0, Vec::new()),
+                }
+            } else if seen_inodes.insert((stat.st_dev as u64, stat.st_ino as u64)) {
+                // Only the first time we see this (dev, inode) pair counts -- later hard links are free.
+                (if disk_usage { stat.st_blocks as u64 * 512 } else { stat.st_size as u64 }, Vec::new())
+            } else {
+                (0, Vec::new())
+            };
+            entries.push(SizedEntry { name, is_dir, size, children });
+        }
+    }
+    entries
+}
+
+// Prints a dutree-style recursive breakdown, descending up to `max_depth` levels. Siblings are sorted by
+// size descending; anything under `threshold` bytes is folded into a single synthetic `<aggregated>` row
+// so huge trees (e.g. `node_modules`) stay readable. Operates purely on the already-built `SizedEntry`
+// tree, so printing never touches the filesystem.
+fn print_sized_tree(entries: &[SizedEntry], depth: usize, max_depth: usize, threshold: u64, opts: &ListOptions) {
+    let mut rows: Vec<(&str, bool, u64, &[SizedEntry])> = Vec::new(); // (name, is_dir, size, children)
+    let mut aggregated = 0u64;
+    for entry in entries {
+        if entry.size < threshold {
+            aggregated += entry.size; // too small to show on its own, fold into the parent's synthetic row
+        } else {
+            rows.push((&entry.name, entry.is_dir, entry.size, &entry.children));
+        }
+    }
+    let aggregated_row = ("<aggregated>", false, aggregated, [].as_slice());
+    if aggregated > 0 { rows.push(aggregated_row); }
+    rows.sort_by(|a, b| b.2.cmp(&a.2)); // biggest subtree first
+
+    let max_size = rows.iter().map(|r| r.2).max().unwrap_or(1).max(1);
+    for (name, is_dir, size, children) in rows {
+        println!("{}{} {:>9} {}", "  ".repeat(depth), render_bar(size, max_size), format_size(size, opts.bytes), name);
+        if is_dir && depth + 1 < max_depth {
+            print_sized_tree(children, depth + 1, max_depth, threshold, opts);
+        }
+    }
+}
+
+// Entry point for tree mode: builds the sized tree in one filesystem walk, then prints it.
+fn print_tree(dir: &Dir, depth: usize, max_depth: usize, threshold: u64, filters: &Filters, opts: &ListOptions) {
+    let mut seen_inodes = HashSet::new();
+    let entries = build_sized_tree(dir, filters, opts.disk_usage, &mut seen_inodes);
+    print_sized_tree(&entries, depth, max_depth, threshold, opts);
+}
+
+// Renders a proportional bar of Unicode block glyphs, scaled so the largest sibling fills `WIDTH` cells.
+fn render_bar(size: u64, max_size: u64) -> String {
+    const WIDTH: usize = 20;
+    let filled = ((size as f64 / max_size as f64) * WIDTH as f64).round() as usize;
+    format!("{}{}", "█".repeat(filled.min(WIDTH)), "░".repeat(WIDTH - filled.min(WIDTH)))
+}
+
+// Parses an `--aggr` threshold: a bare number of bytes, or a number suffixed with K/M/G (1024-based,
+// matching the units `convert_binary_units` formats sizes with).
+fn parse_threshold(spec: &str) -> u64 {
+    let spec = spec.trim();
+    let (digits, multiplier) = match spec.chars().last() {
+        Some('K') | Some('k') => (&spec[..spec.len() - 1], 1024),
+        Some('M') | Some('m') => (&spec[..spec.len() - 1], 1024 * 1024),
+        Some('G') | Some('g') => (&spec[..spec.len() - 1], 1024 * 1024 * 1024),
+        _ => (spec, 1)
+    };
+    digits.trim().parse::<u64>().unwrap_or(0) * multiplier
+}
 
 // converts raw bytes (u64) into readable strings (KB, MB, GB).
 fn convert_binary_units(size: u64) -> String {